@@ -0,0 +1,202 @@
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixListener,
+    sync::{mpsc, Mutex},
+};
+
+use crate::{
+    last_fm::{BasicInfo, SongInfo},
+    Message,
+};
+
+/// State shared between the scrobble task and the control socket listener,
+/// so a control command can be answered without
+/// round-tripping through the (queued, backend-facing) `Message` channel.
+#[derive(Default)]
+pub struct ControlState {
+    current_song: Mutex<Option<SongInfo>>,
+    queue_depth: AtomicUsize,
+    authenticated: AtomicBool,
+}
+
+impl ControlState {
+    pub async fn set_current_song(&self, song: Option<SongInfo>) {
+        *self.current_song.lock().await = song;
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.store(depth, Ordering::Relaxed);
+    }
+
+    pub fn set_authenticated(&self, value: bool) {
+        self.authenticated.store(value, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Command {
+    Status,
+    Flush,
+    Love,
+    Unlove,
+    Rate(u8),
+    /// re-pushes the current track as a fresh now-playing update, e.g. after
+    /// the client suspects scritches missed a player event
+    NowPlaying,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusPayload {
+    current_song: Option<SongInfo>,
+    queue_depth: usize,
+    authenticated: bool,
+}
+
+/// Tagged `Success`/`Failure`/`Fatal` response, so a client can tell apart a
+/// transient failure worth retrying (e.g. nothing is playing right now) from
+/// a fatal one (e.g. the scrobble task has exited and won't come back).
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "payload")]
+enum Response {
+    Success(Option<StatusPayload>),
+    Failure(String),
+    Fatal(String),
+}
+
+/// Builds the `Message` a `Love`/`Unlove`/`Rate` command would send for
+/// whatever's currently playing, or `None` if nothing is.
+async fn current_track_message(
+    state: &ControlState,
+    message: impl FnOnce(BasicInfo) -> Message,
+) -> Option<Message> {
+    let info = state
+        .current_song
+        .lock()
+        .await
+        .as_ref()
+        .map(BasicInfo::from)?;
+    Some(message(info))
+}
+
+async fn respond_with_message(message: Option<Message>, tx: &mpsc::Sender<Message>) -> Response {
+    let Some(message) = message else {
+        return Response::Failure("nothing is currently playing".to_owned());
+    };
+
+    match tx.send(message).await {
+        Ok(()) => Response::Success(None),
+        Err(_) => Response::Fatal("scrobble task is gone".to_owned()),
+    }
+}
+
+async fn handle_command(
+    command: Command,
+    state: &ControlState,
+    tx: &mpsc::Sender<Message>,
+    rating_tx: &mpsc::Sender<u8>,
+) -> Response {
+    match command {
+        Command::Status => Response::Success(Some(StatusPayload {
+            current_song: state.current_song.lock().await.clone(),
+            queue_depth: state.queue_depth.load(Ordering::Relaxed),
+            authenticated: state.authenticated.load(Ordering::Relaxed),
+        })),
+        Command::Flush => match tx.send(Message::Flush).await {
+            Ok(()) => Response::Success(None),
+            Err(_) => Response::Fatal("scrobble task is gone".to_owned()),
+        },
+        Command::Love => {
+            respond_with_message(current_track_message(state, Message::love_track).await, tx).await
+        }
+        Command::Unlove => {
+            respond_with_message(
+                current_track_message(state, Message::unlove_track).await,
+                tx,
+            )
+            .await
+        }
+        // goes to the MPD event loop rather than through `tx`, since writing
+        // the granular rating sticker needs the currently-playing song's
+        // URI, which only the event loop has
+        Command::Rate(value) => {
+            if state.current_song.lock().await.is_none() {
+                return Response::Failure("nothing is currently playing".to_owned());
+            }
+            match rating_tx.send(value).await {
+                Ok(()) => Response::Success(None),
+                Err(_) => Response::Fatal("event loop is gone".to_owned()),
+            }
+        }
+        Command::NowPlaying => {
+            let current_song = state.current_song.lock().await.clone();
+            if current_song.is_none() {
+                return Response::Failure("nothing is currently playing".to_owned());
+            }
+            match tx.send(Message::NowPlaying(current_song)).await {
+                Ok(()) => Response::Success(None),
+                Err(_) => Response::Fatal("scrobble task is gone".to_owned()),
+            }
+        }
+    }
+}
+
+/// Serves the control API on a unix socket: one newline-delimited JSON
+/// [`Command`] in, one newline-delimited JSON [`Response`] out, per
+/// connection.
+pub async fn serve(
+    path: &Path,
+    state: Arc<ControlState>,
+    tx: mpsc::Sender<Message>,
+    rating_tx: mpsc::Sender<u8>,
+) -> io::Result<()> {
+    // a stale socket left behind by an unclean shutdown would otherwise make
+    // bind fail
+    let _ = std::fs::remove_file(path);
+
+    let listener = UnixListener::bind(path)?;
+    info!("serving control API on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = Arc::clone(&state);
+        let tx = tx.clone();
+        let rating_tx = rating_tx.clone();
+
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            let response = match lines.next_line().await {
+                Ok(Some(line)) => match serde_json::from_str::<Command>(&line) {
+                    Ok(command) => handle_command(command, &state, &tx, &rating_tx).await,
+                    Err(e) => Response::Failure(format!("invalid command: {e}")),
+                },
+                Ok(None) => return,
+                Err(e) => {
+                    warn!("control socket read failed: {e}");
+                    return;
+                }
+            };
+
+            let Ok(mut body) = serde_json::to_string(&response) else {
+                return;
+            };
+            body.push('\n');
+
+            if let Err(e) = writer.write_all(body.as_bytes()).await {
+                warn!("control socket write failed: {e}");
+            }
+        });
+    }
+}
@@ -1,5 +1,6 @@
-use std::{fmt, fs, path::Path, sync::LazyLock, time::Duration};
+use std::{fmt, path::Path, sync::LazyLock, time::Duration};
 
+use async_trait::async_trait;
 use log::{debug, error, info, trace, warn};
 use md5::{Digest, Md5};
 use mpd_client::responses::{Song, SongInQueue};
@@ -7,6 +8,11 @@ use reqwest::{Client as HttpClient, RequestBuilder};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tokio::time::interval;
 
+use crate::{
+    scrobbler::{IgnoredReason, RecentTrack, ScrobbleOutcome, Scrobbler, MAX_SCROBBLE_BATCH},
+    secret_store::{FileStore, KeyringStore, SecretBackend, SecretStore},
+};
+
 static API_KEY: &str = "936df272ba862808520323da81f3fc6e";
 static API_SECRET: &str = "d401bc1f1a702af8e6bd8c50bce9b11d";
 static API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
@@ -39,7 +45,7 @@ pub enum SongError {
     NoArtist,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SongInfo {
     pub title: String,
     pub artist: String,
@@ -109,7 +115,7 @@ impl<'a> PushParams<'a, SongInfo> for Vec<(&str, &'a str)> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BasicInfo {
     pub title: String,
     pub artist: String,
@@ -135,6 +141,15 @@ impl TryFrom<&SongInQueue> for BasicInfo {
     }
 }
 
+impl From<&SongInfo> for BasicInfo {
+    fn from(info: &SongInfo) -> Self {
+        Self {
+            title: info.title.clone(),
+            artist: info.artist.clone(),
+        }
+    }
+}
+
 impl<'a> PushParams<'a, BasicInfo> for Vec<(&str, &'a str)> {
     fn push_params(&mut self, info: &'a BasicInfo) {
         self.push(("track", &info.title));
@@ -169,6 +184,116 @@ pub struct ApiError {
     message: String,
 }
 
+/// Maps the numeric `ignoredMessage` code from a `track.scrobble` response
+/// onto a reason, per last.fm's docs (0 = accepted).
+fn ignored_reason_from_code(code: u8) -> Option<IgnoredReason> {
+    match code {
+        1 => Some(IgnoredReason::ArtistIgnored),
+        2 => Some(IgnoredReason::TrackIgnored),
+        3 => Some(IgnoredReason::TimestampTooOld),
+        4 => Some(IgnoredReason::TimestampTooNew),
+        5 => Some(IgnoredReason::RateLimited),
+        _ => None,
+    }
+}
+
+fn deserialize_code<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct CodeVisitor;
+
+    impl serde::de::Visitor<'_> for CodeVisitor {
+        type Value = u8;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a scrobble ignore code")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<u8, E>
+        where
+            E: serde::de::Error,
+        {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<u8, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v as u8)
+        }
+    }
+
+    deserializer.deserialize_any(CodeVisitor)
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    match OneOrMany::<T>::deserialize(deserializer)? {
+        OneOrMany::One(t) => Ok(vec![t]),
+        OneOrMany::Many(v) => Ok(v),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IgnoredMessage {
+    #[serde(rename = "code", deserialize_with = "deserialize_code")]
+    code: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrobbleResult {
+    #[serde(rename = "ignoredMessage")]
+    ignored_message: IgnoredMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrobblesAttr {
+    #[allow(dead_code)]
+    accepted: u32,
+    #[allow(dead_code)]
+    ignored: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Scrobbles {
+    #[serde(rename = "@attr")]
+    #[allow(dead_code)]
+    attr: ScrobblesAttr,
+    #[serde(rename = "scrobble", deserialize_with = "one_or_many")]
+    scrobble: Vec<ScrobbleResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrobbleResponse {
+    scrobbles: Scrobbles,
+}
+
+impl ScrobbleResponse {
+    fn into_outcomes(self) -> Vec<ScrobbleOutcome> {
+        self.scrobbles
+            .scrobble
+            .into_iter()
+            .map(|s| match ignored_reason_from_code(s.ignored_message.code) {
+                None => ScrobbleOutcome::Accepted,
+                Some(reason) if reason.is_transient() => ScrobbleOutcome::Retry,
+                Some(reason) => ScrobbleOutcome::Dropped(reason),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("too many scrobbles in batch. maximum is 50 got {0}")]
@@ -208,6 +333,71 @@ impl Error {
     pub fn is_retryable(&self) -> bool {
         matches!(self, Self::Http(_) | Self::ApiRetry(_, _))
     }
+
+    #[inline]
+    pub fn is_reauth(&self) -> bool {
+        matches!(self, Self::ApiReauth(_, _))
+    }
+}
+
+fn parse_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Deserialize)]
+struct TextField {
+    #[serde(rename = "#text")]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DateField {
+    #[serde(deserialize_with = "parse_from_str")]
+    uts: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTrack {
+    artist: TextField,
+    name: String,
+    // absent for the currently-playing track
+    date: Option<DateField>,
+}
+
+impl RawTrack {
+    fn into_recent(self) -> Option<RecentTrack> {
+        let date = self.date?;
+        Some(RecentTrack {
+            artist: self.artist.text,
+            title: self.name,
+            timestamp: date.uts,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTracksAttr {
+    #[serde(rename = "totalPages", deserialize_with = "parse_from_str")]
+    total_pages: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTracksInner {
+    #[serde(rename = "track", default)]
+    track: Vec<RawTrack>,
+    #[serde(rename = "@attr")]
+    attr: RecentTracksAttr,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTracksResponse {
+    recenttracks: RecentTracksInner,
 }
 
 struct SignedParams<'a, 'b> {
@@ -272,64 +462,72 @@ where
 
 pub struct Client {
     session_key: String,
+    username: Option<String>,
     client: HttpClient,
 }
 
 impl Client {
-    // awful awful hack to deal with opaque future types, constructor can take a previous client to
-    // reauth it instead of actually creating a new one
     pub async fn new(
-        prev_client: Option<Self>,
         sk_path: &Path,
+        username_path: &Path,
+        secret_backend: SecretBackend,
         non_interactive: bool,
+        force_reauth: bool,
     ) -> Result<Self, Error> {
-        if let Some(prev_client) = prev_client {
-            if non_interactive {
-                return Err(Error::NonInteractive);
-            }
-            return prev_client.re_auth(sk_path).await;
-        }
-
         let client = HttpClient::new();
 
-        let session_key = match Self::retrieve_sk(sk_path) {
-            Some(sk) => sk,
+        // a forced reauth (after an `ApiReauth` from the API) bypasses the
+        // stored key entirely, otherwise we'd just reload the same stale key
+        // and hit the same error again
+        let stored = if force_reauth {
+            None
+        } else {
+            Self::retrieve_sk(sk_path, secret_backend)
+        };
+
+        let (session_key, username) = match stored {
+            Some(sk) => (sk, Self::retrieve_username(username_path)),
             None => {
                 if non_interactive {
                     Err(Error::NonInteractive)
                 } else {
-                    Self::authenticate(&client, sk_path).await
+                    Self::authenticate(&client, sk_path, username_path, secret_backend)
+                        .await
+                        .map(|(sk, user)| (sk, Some(user)))
                 }?
             }
         };
 
         Ok(Self {
             session_key,
+            username,
             client,
         })
     }
 
-    // TODO: want this to be able to persist session key in dbus secrets service if available
-    // instead of just in a file
-    fn retrieve_sk(path: &Path) -> Option<String> {
-        match std::fs::read_to_string(path) {
-            Err(e) => {
-                warn!("couldn't read session key from `{}`: {e}", path.display());
-                None
-            }
-            Ok(sk) => Some(sk),
+    fn retrieve_sk(path: &Path, secret_backend: SecretBackend) -> Option<String> {
+        match secret_backend {
+            SecretBackend::File => FileStore::new(path).load(),
+            SecretBackend::SecretService => KeyringStore::new(None, path).load(),
         }
     }
 
-    async fn re_auth(mut self, sk_path: &Path) -> Result<Self, Error> {
-        let session_key = Self::authenticate(&self.client, sk_path).await?;
-
-        self.session_key = session_key;
+    fn retrieve_username(path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
 
-        Ok(self)
+    fn store_username(path: &Path, username: &str) {
+        if let Err(e) = std::fs::write(path, username) {
+            warn!("failed to persist last.fm username: {e}");
+        }
     }
 
-    async fn authenticate(client: &HttpClient, path: &Path) -> Result<String, Error> {
+    async fn authenticate(
+        client: &HttpClient,
+        path: &Path,
+        username_path: &Path,
+        secret_backend: SecretBackend,
+    ) -> Result<(String, String), Error> {
         #[derive(Debug, Deserialize)]
         struct Token {
             token: String,
@@ -390,18 +588,19 @@ impl Client {
             session.key, session.name
         );
 
-        if let Err(e) = fs::write(path, &session.key) {
-            warn!("failed to persist session key: {e}");
+        match secret_backend {
+            SecretBackend::File => FileStore::new(path).store(&session.key),
+            SecretBackend::SecretService => {
+                KeyringStore::new(Some(&session.name), path).store(&session.key);
+            }
         }
 
-        Ok(session.key)
+        Self::store_username(username_path, &session.name);
+
+        Ok((session.key, session.name))
     }
 
-    /* async fn method_call<T>(
-        &self,
-        method: &str,
-        args: Option<Vec<(&str, &str)>>,
-    ) -> Result<T, Error>
+    async fn method_call<T>(&self, method: &str, args: Option<Vec<(&str, &str)>>) -> Result<T, Error>
     where
         T: DeserializeOwned,
     {
@@ -417,24 +616,19 @@ impl Client {
 
         let client = &self.client;
 
-        let signed = sign(params);
-        let request = client.post(API_URL).form(
-            &signed
-                .params
-                .iter()
-                .chain(vec![("api_sig", &signed.signature[..]), ("format", "json")].iter())
-                .collect::<Vec<_>>(),
-        );
-        let response = request.send().await?.text().await?;
-
-        println!("{response}");
+        let response = sign(params)
+            .into_request(client)
+            .send()
+            .await?
+            .text()
+            .await?;
 
         if let Ok(e) = serde_json::from_str::<ApiError>(&response) {
             return Err(e.into());
         }
 
         Ok(serde_json::from_str(&response)?)
-    } */
+    }
 
     async fn void_method(
         &self,
@@ -467,28 +661,31 @@ impl Client {
         Ok(())
     }
 
-    pub async fn scrobble_one(&mut self, info: &SongInfo, timestamp: &str) -> Result<(), Error> {
-        trace!("scrobble:{info:#?} timestamp: {timestamp}");
-        let mut params = Vec::new();
-
-        params.push_params(info);
-        params.push(("timestamp", timestamp));
-
-        self.void_method("track.scrobble", Some(params)).await
-    }
-
-    pub async fn scrobble_many(&mut self, infos: &[(SongInfo, String)]) -> Result<(), Error> {
-        if infos.len() > 50 {
+    pub async fn scrobble_many(
+        &mut self,
+        infos: &[(SongInfo, String)],
+    ) -> Result<Vec<ScrobbleOutcome>, Error> {
+        if infos.len() > MAX_SCROBBLE_BATCH {
             return Err(Error::TooManyScrobbles(infos.len()));
         }
-        let mut params = Vec::new();
 
-        for (i, (info, timestamp)) in infos.iter().enumerate() {
-            params.push_params_idx(info, i);
-            params.push((&TIMESTAMP[i], timestamp));
+        // a lone scrobble (the common case: a single live play with nothing
+        // else queued) goes through the plain, non-indexed params rather
+        // than the batch form, same as the old single-track `scrobble_one`
+        let mut params = Vec::new();
+        if let [(info, timestamp)] = infos {
+            params.push_params(info);
+            params.push(("timestamp", timestamp));
+        } else {
+            for (i, (info, timestamp)) in infos.iter().enumerate() {
+                params.push_params_idx(info, i);
+                params.push((&TIMESTAMP[i], timestamp));
+            }
         }
 
-        self.void_method("track.scrobble", Some(params)).await
+        let response: ScrobbleResponse = self.method_call("track.scrobble", Some(params)).await?;
+
+        Ok(response.into_outcomes())
     }
 
     pub async fn now_playing(&mut self, info: &SongInfo) -> Result<(), Error> {
@@ -512,4 +709,66 @@ impl Client {
             Action::Unlove => self.void_method("track.unlove", Some(params)).await,
         }
     }
+
+    /// Fetches one page of `user.getRecentTracks`, returning the tracks on
+    /// that page along with the total number of pages. Returns an empty page
+    /// if we don't yet know the authorized username.
+    pub async fn recent_tracks(&self, page: u32) -> Result<(Vec<RecentTrack>, u32), Error> {
+        let Some(username) = self.username.as_deref() else {
+            return Ok((Vec::new(), 1));
+        };
+
+        let page = page.to_string();
+        let params = vec![("user", username), ("page", &page[..]), ("limit", "200")];
+
+        let response: RecentTracksResponse =
+            unauth_method_call("user.getRecentTracks", Some(params), &self.client).await?;
+
+        let total_pages = response.recenttracks.attr.total_pages;
+        let tracks = response
+            .recenttracks
+            .track
+            .into_iter()
+            .filter_map(RawTrack::into_recent)
+            .collect();
+
+        Ok((tracks, total_pages))
+    }
+}
+
+#[async_trait]
+impl Scrobbler for Client {
+    fn name(&self) -> &'static str {
+        "last.fm"
+    }
+
+    async fn now_playing(&mut self, info: &SongInfo) -> Result<(), crate::scrobbler::Error> {
+        Ok(Self::now_playing(self, info).await?)
+    }
+
+    async fn scrobble_many(
+        &mut self,
+        infos: &[(SongInfo, String)],
+    ) -> Result<Vec<ScrobbleOutcome>, crate::scrobbler::Error> {
+        Ok(Self::scrobble_many(self, infos).await?)
+    }
+
+    async fn do_track_action(
+        &mut self,
+        action: Action,
+        info: &BasicInfo,
+    ) -> Result<(), crate::scrobbler::Error> {
+        Ok(Self::do_track_action(self, action, info).await?)
+    }
+
+    fn supports_reconcile(&self) -> bool {
+        true
+    }
+
+    async fn recent_tracks(
+        &self,
+        page: u32,
+    ) -> Result<(Vec<RecentTrack>, u32), crate::scrobbler::Error> {
+        Ok(Self::recent_tracks(self, page).await?)
+    }
 }
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use log::trace;
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    last_fm::{Action, BasicInfo, SongInfo},
+    scrobbler::{Error, RecentTrack, ScrobbleOutcome, Scrobbler},
+};
+
+static SUBMIT_URL: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+#[derive(Debug, Serialize)]
+struct TrackMetadata<'a> {
+    artist_name: &'a str,
+    track_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_name: Option<&'a str>,
+}
+
+impl<'a> From<&'a SongInfo> for TrackMetadata<'a> {
+    fn from(info: &'a SongInfo) -> Self {
+        Self {
+            artist_name: &info.artist,
+            track_name: &info.title,
+            release_name: info.album.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listened_at: Option<i64>,
+    track_metadata: TrackMetadata<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct SubmitListens<'a> {
+    listen_type: &'static str,
+    payload: Vec<Payload<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    error: String,
+}
+
+pub struct Client {
+    token: String,
+    client: HttpClient,
+}
+
+impl Client {
+    pub fn new(token: String) -> Self {
+        Self {
+            token,
+            client: HttpClient::new(),
+        }
+    }
+
+    async fn submit(&self, body: &SubmitListens<'_>) -> Result<(), Error> {
+        let response = self
+            .client
+            .post(SUBMIT_URL)
+            .header("Authorization", format!("Token {}", self.token))
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ApiError>(&text)
+            .map(|e| e.error)
+            .unwrap_or(text);
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Err(Error::Retry(format!("{message} ({status})"))),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(Error::Reauth(message)),
+            s if s.is_server_error() => Err(Error::Retry(format!("{message} ({status})"))),
+            _ => Err(Error::Fatal(format!("{message} ({status})"))),
+        }
+    }
+}
+
+#[async_trait]
+impl Scrobbler for Client {
+    fn name(&self) -> &'static str {
+        "listenbrainz"
+    }
+
+    async fn now_playing(&mut self, info: &SongInfo) -> Result<(), Error> {
+        trace!("now_playing: {info:#?}");
+        self.submit(&SubmitListens {
+            listen_type: "playing_now",
+            payload: vec![Payload {
+                listened_at: None,
+                track_metadata: info.into(),
+            }],
+        })
+        .await
+    }
+
+    async fn scrobble_many(
+        &mut self,
+        infos: &[(SongInfo, String)],
+    ) -> Result<Vec<ScrobbleOutcome>, Error> {
+        let payload = infos
+            .iter()
+            .map(|(info, timestamp)| Payload {
+                listened_at: timestamp.parse().ok(),
+                track_metadata: info.into(),
+            })
+            .collect();
+
+        let listen_type = if infos.len() == 1 { "single" } else { "import" };
+
+        self.submit(&SubmitListens {
+            listen_type,
+            payload,
+        })
+        .await?;
+
+        // ListenBrainz doesn't report per-track ignore reasons like last.fm
+        // does; a successful response means every listen in the batch stuck.
+        Ok(vec![ScrobbleOutcome::Accepted; infos.len()])
+    }
+
+    async fn do_track_action(&mut self, action: Action, info: &BasicInfo) -> Result<(), Error> {
+        // ListenBrainz's love/hate feedback is keyed on a recording MSID or
+        // MBID, neither of which we have, so there's nothing to submit here.
+        trace!("{action}e not supported by listenbrainz, ignoring: {info:#?}");
+        Ok(())
+    }
+}
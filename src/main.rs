@@ -22,18 +22,31 @@ use tokio_util::sync::CancellationToken;
 
 use std::{
     cmp::min,
+    io,
     path::Path,
+    sync::Arc,
     time::SystemTime,
     time::{Duration, UNIX_EPOCH},
 };
 
+mod control;
 mod last_fm;
+mod listenbrainz;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod scrobbler;
+mod secret_store;
 mod settings;
+mod stickers;
 mod work_queue;
 
 use crate::{
+    control::ControlState,
     last_fm::{Client as LastFmClient, Error as LastFmError},
-    settings::Args,
+    scrobbler::Error as ScrobblerError,
+    secret_store::SecretBackend,
+    settings::{Args, StickerSettings},
+    stickers::StickerQueue,
     work_queue::WorkQueue,
 };
 
@@ -44,18 +57,25 @@ enum MsgHandleError {
 
     /// unrecoverable API errors
     #[error(transparent)]
-    LastFmFatal(LastFmError),
+    Fatal(ScrobblerError),
 
     #[error(transparent)]
-    LastFmReauth(LastFmError),
+    Reauth(ScrobblerError),
+}
+
+impl From<ScrobblerError> for MsgHandleError {
+    fn from(e: ScrobblerError) -> Self {
+        if e.is_reauth() {
+            Self::Reauth(e)
+        } else {
+            Self::Fatal(e)
+        }
+    }
 }
 
 impl From<LastFmError> for MsgHandleError {
     fn from(e: LastFmError) -> Self {
-        match e {
-            LastFmError::ApiReauth(_, _) => Self::LastFmReauth(e),
-            _ => Self::LastFmFatal(e),
-        }
+        ScrobblerError::from(e).into()
     }
 }
 
@@ -64,6 +84,9 @@ enum Message {
     Scrobble(SongInfo, String),
     NowPlaying(Option<SongInfo>),
     TrackAction(Action, BasicInfo),
+    /// Asks the scrobble task to drain the work queue immediately, rather
+    /// than waiting for its retry timer, requested over the control socket.
+    Flush,
 }
 
 impl Message {
@@ -93,6 +116,52 @@ impl Connector {
     }
 }
 
+/// Opens a stream to MPD, preferring the unix socket and falling back to
+/// TCP if it's unset or unreachable.
+async fn open_stream(addr: &str, socket: Option<&Path>) -> io::Result<Connector> {
+    if let Some(sock) = socket {
+        info!("connecting to MPD at {}", sock.display());
+        match UnixStream::connect(sock).await {
+            Ok(stream) => return Ok(Connector::Uds(stream)),
+            Err(e) => warn!("failed to connect to unix socket `{}`: {e}", sock.display()),
+        }
+    }
+
+    info!("connecting to MPD at {addr}");
+    TcpStream::connect(addr).await.map(Connector::Tcp)
+}
+
+/// Connects to MPD, retrying with exponential backoff (the same 15s-doubling
+/// scheme `scrobble_task` uses, capped at `max_retry_time`) until it
+/// succeeds, so a restarting `mpd` doesn't kill the daemon.
+async fn connect_mpd(
+    addr: &str,
+    socket: Option<&Path>,
+    password: Option<&str>,
+    max_retry_time: Duration,
+) -> Connection {
+    let mut retry_time = Duration::from_secs(15);
+
+    loop {
+        let attempt = match open_stream(addr, socket).await {
+            Ok(connector) => connector.connect(password).await.map_err(anyhow::Error::from),
+            Err(e) => Err(anyhow::Error::from(e)),
+        };
+
+        match attempt {
+            Ok(conn) => return conn,
+            Err(e) => {
+                warn!(
+                    "failed to connect to MPD: {e}, retrying in {}s",
+                    retry_time.as_secs()
+                );
+                tokio::time::sleep(retry_time).await;
+                retry_time = min(max_retry_time, retry_time * 2);
+            }
+        }
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     env_logger::builder().format_timestamp(None).init();
@@ -110,125 +179,199 @@ async fn main_inner() -> anyhow::Result<()> {
 
     let settings = settings::Settings::new(args)?;
 
-    let conn: Connector = if let Some(sock) = settings.mpd_socket {
-        info!("connecting to MPD at {}", sock.display());
-        match UnixStream::connect(&sock).await {
-            Ok(sock) => Connector::Uds(sock),
-            Err(e) => {
-                warn!("failed to connect to unix socket `{}`: {e}", sock.display(),);
-                info!("connecting to MPD at {}", settings.mpd_addr);
-                Connector::Tcp(TcpStream::connect(&settings.mpd_addr).await?)
-            }
-        }
-    } else {
-        info!("connecting to MPD at {}", settings.mpd_addr);
-        Connector::Tcp(TcpStream::connect(&settings.mpd_addr).await?)
-    };
-
-    let (client, mut state_changes) = conn.connect(settings.mpd_password.as_deref()).await?;
+    let max_retry_time = Duration::from_secs(settings.max_retry_time);
+    let mpd_addr = settings.mpd_addr.clone();
+    let mpd_socket = settings.mpd_socket.clone();
+    let mpd_password = settings.mpd_password.clone();
+
+    let (mut client, mut state_changes) = connect_mpd(
+        &mpd_addr,
+        mpd_socket.as_deref(),
+        mpd_password.as_deref(),
+        max_retry_time,
+    )
+    .await;
 
     info!("connected!");
 
     let (tx, mut rx) = mpsc::channel(5);
 
-    let mut work_queue = WorkQueue::new(settings.queue_path)?;
+    let mut work_queue = WorkQueue::new(&settings.queue_path, settings.reconcile_recent_tracks)?;
 
-    let max_retry_time = Duration::from_secs(settings.max_retry_time);
+    if let Some(token) = settings.listenbrainz_token.clone() {
+        work_queue.add_backend(Box::new(listenbrainz::Client::new(token)));
+    }
+
+    let sticker_settings = settings.stickers.clone();
+    let mut sticker_queue = StickerQueue::new(&settings.sticker_queue_path)?;
+
+    let control_state = Arc::new(ControlState::default());
+
+    // ratings from the control socket go straight to the MPD event loop
+    // below rather than through `tx`/`work_queue`, since writing the rating
+    // sticker needs the currently-playing song's URI, which only the event
+    // loop has
+    let (rating_tx, mut rating_rx) = mpsc::channel(5);
+
+    if let Some(control_socket) = settings.control_socket.clone() {
+        let control_state = Arc::clone(&control_state);
+        let tx = tx.clone();
+        let rating_tx = rating_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(&control_socket, control_state, tx, rating_tx).await {
+                error!("control socket failed: {e}");
+            }
+        });
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics_settings) = settings.metrics.clone() {
+        tokio::spawn(metrics::push_task(metrics_settings, Duration::from_secs(15)));
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics_addr) = settings.metrics_addr.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&metrics_addr).await {
+                error!("metrics server failed: {e}");
+            }
+        });
+    }
 
     let cancel_token = CancellationToken::new();
     let cloned_token = cancel_token.clone();
 
+    let scrobble_control_state = Arc::clone(&control_state);
+
     //TODO: more graceful shutdown
     tokio::spawn(async move {
-        let mut prev_client = None;
-        let mut err;
-
+        // forces the next `LastFmClient::new` to skip the stored session key
+        // and go through interactive `authenticate()` instead, set after an
+        // `ApiReauth` so we don't just reload the same stale key and spin
+        let mut force_reauth = false;
         loop {
-            (prev_client, err) = scrobble_task(
+            let err = scrobble_task(
                 &mut rx,
                 &mut work_queue,
-                prev_client,
                 &settings.sk_path,
+                &settings.username_path,
+                settings.secret_backend,
                 max_retry_time,
                 non_interactive,
+                force_reauth,
+                &scrobble_control_state,
             )
             .await;
 
+            force_reauth = false;
+
             match err {
                 MsgHandleError::ChannelClosed => info!("message channel closed"),
-                MsgHandleError::LastFmFatal(_) => cloned_token.cancel(),
-                MsgHandleError::LastFmReauth(_) => continue,
+                MsgHandleError::Fatal(_) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::inc_fatal_errors();
+                    cloned_token.cancel();
+                }
+                MsgHandleError::Reauth(_) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::inc_reauths();
+                    force_reauth = true;
+                    continue;
+                }
             }
             break;
         }
     });
 
-    client.command(SubscribeToChannel("scritches")).await?;
+    loop {
+        client.command(SubscribeToChannel("scritches")).await?;
 
-    let stats = client.command(Stats).await?;
-    let status = client.command(Status).await?;
+        let stats = client.command(Stats).await?;
+        let status = client.command(Status).await?;
 
-    let elapsed = status.elapsed.unwrap_or_default();
-    let mut length = status.duration.unwrap_or_default();
-    let mut start_playtime = stats.playtime - elapsed;
-    let mut current_song = client.command(CurrentSong).await?;
+        let elapsed = status.elapsed.unwrap_or_default();
+        let mut length = status.duration.unwrap_or_default();
+        let mut start_playtime = stats.playtime - elapsed;
+        let mut current_song = client.command(CurrentSong).await?;
 
-    let mut start_time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let mut start_time = SystemTime::now().duration_since(UNIX_EPOCH)?;
 
-    if cancel_token.is_cancelled() {
-        return Err(anyhow!("unrecoverable error, shutting down"));
-    }
+        if cancel_token.is_cancelled() {
+            return Err(anyhow!("unrecoverable error, shutting down"));
+        }
 
-    if let Some(Ok(info)) = current_song.as_ref().map(TryInto::try_into) {
-        tx.send(Message::NowPlaying(Some(info))).await?;
-    }
+        if let Some(Ok(info)) = current_song.as_ref().map(TryInto::try_into) {
+            tx.send(Message::NowPlaying(Some(info))).await?;
+        }
 
-    loop {
-        select! {
-        _ = cancel_token.cancelled() => return Err(anyhow!("unrecoverable error, shutting down")),
-        s = tokio::signal::ctrl_c() => match s {
-            Ok(_) => {
-                eprintln!();
-                break;
-            }
-            // why would this ever happen?
-            Err(e) => {
-                eprintln!();
-                error!("huh? {e}");
-                break;
-            }
-        },
-        n = state_changes.next() => match n {
-            Some(ConnectionEvent::SubsystemChange(Subsystem::Player)) => {
-                (length, start_playtime, start_time, current_song) = handle_player(
-                    &client,
-                    &tx,
-                    length,
-                    start_playtime,
-                    start_time,
-                    current_song,
-                )
-                .await?;
-            }
-            Some(ConnectionEvent::SubsystemChange(Subsystem::Message)) => {
-                if let Some(song) = current_song.as_ref() {
-                    handle_mpd_msg(&client, &tx, song).await?;
+        loop {
+            select! {
+            _ = cancel_token.cancelled() => return Err(anyhow!("unrecoverable error, shutting down")),
+            s = tokio::signal::ctrl_c() => match s {
+                Ok(_) => {
+                    eprintln!();
+                    return Ok(());
+                }
+                // why would this ever happen?
+                Err(e) => {
+                    eprintln!();
+                    error!("huh? {e}");
+                    return Ok(());
+                }
+            },
+            n = state_changes.next() => match n {
+                Some(ConnectionEvent::SubsystemChange(Subsystem::Player)) => {
+                    (length, start_playtime, start_time, current_song) = handle_player(
+                        &client,
+                        &tx,
+                        &mut sticker_queue,
+                        &sticker_settings,
+                        length,
+                        start_playtime,
+                        start_time,
+                        current_song,
+                    )
+                    .await?;
+                }
+                Some(ConnectionEvent::SubsystemChange(Subsystem::Message)) => {
+                    if let Some(song) = current_song.as_ref() {
+                        handle_mpd_msg(&client, &tx, &mut sticker_queue, &sticker_settings, song).await?;
+                    }
+                }
+                Some(ConnectionEvent::SubsystemChange(_)) => continue,
+                _ => {
+                    warn!("lost connection to MPD, reconnecting");
+                    break;
+                }
+            },
+            Some(value) = rating_rx.recv() => {
+                handle_rate(&tx, &mut sticker_queue, &sticker_settings, current_song.as_ref(), value).await?;
+            }}
+
+            if sticker_queue.has_work() {
+                if let Err(e) = sticker_queue.do_work(&client).await {
+                    warn!("failed to write MPD stickers, will retry: {e}");
                 }
             }
-            Some(ConnectionEvent::SubsystemChange(_)) => continue,
-            _ => {
-                error!("lost connection to MPD");
-                break;
-            }
-        }}
-    }
+        }
 
-    Ok(())
+        (client, state_changes) = connect_mpd(
+            &mpd_addr,
+            mpd_socket.as_deref(),
+            mpd_password.as_deref(),
+            max_retry_time,
+        )
+        .await;
+
+        info!("reconnected!");
+    }
 }
 
 async fn handle_player(
     client: &MpdClient,
     tx: &mpsc::Sender<Message>,
+    sticker_queue: &mut StickerQueue,
+    stickers: &StickerSettings,
     length: Duration,
     start_playtime: Duration,
     start_time: Duration,
@@ -256,6 +399,7 @@ async fn handle_player(
                     Ok(info) => {
                         let timestamp = start_time.as_secs().to_string();
                         tx.send(Message::Scrobble(info, timestamp)).await?;
+                        record_play(sticker_queue, stickers, song.song.url()).await;
                     }
                 }
                 Ok((
@@ -276,6 +420,7 @@ async fn handle_player(
                         Ok(info) => {
                             let timestamp = start_time.as_secs().to_string();
                             tx.send(Message::Scrobble(info, timestamp)).await?;
+                            record_play(sticker_queue, stickers, song.song.url()).await;
                         }
                     }
                 }
@@ -300,6 +445,8 @@ async fn handle_player(
 async fn handle_mpd_msg(
     client: &MpdClient,
     tx: &mpsc::Sender<Message>,
+    sticker_queue: &mut StickerQueue,
+    stickers: &StickerSettings,
     current_song: &SongInQueue,
 ) -> anyhow::Result<()> {
     let info = current_song.try_into()?;
@@ -307,12 +454,36 @@ async fn handle_mpd_msg(
     let messages = client.command(ReadChannelMessages).await?;
 
     let mut love = true;
+    let mut rating = None;
 
     for m in messages {
         if m.1 == "love" {
             love = true;
+            rating = Some(100);
         } else if m.1 == "unlove" {
             love = false;
+            rating = Some(0);
+        } else if let Some(value) = m.1.strip_prefix("rating ") {
+            match value.trim().parse::<u8>() {
+                Ok(value) => {
+                    let value = value.min(100);
+                    // keep love/unlove consistent with the rating, same as
+                    // `handle_rate` derives it for the control socket
+                    love = value >= 50;
+                    rating = Some(value);
+                }
+                Err(_) => warn!("invalid rating message: {value}"),
+            }
+        }
+    }
+
+    if let Some(rating) = rating {
+        if stickers.ratings_enabled {
+            sticker_queue.queue_set(
+                current_song.song.url().to_owned(),
+                stickers.rating_sticker.clone(),
+                rating.to_string(),
+            );
         }
     }
 
@@ -325,24 +496,99 @@ async fn handle_mpd_msg(
     Ok(())
 }
 
+/// Applies a 0-100 rating requested over the control socket to whatever's
+/// currently playing: queues the granular rating sticker (same as the MPD
+/// `rating <0-100>` channel message) and forwards a binary love/unlove
+/// action to the scrobbler. No-op if nothing is playing.
+async fn handle_rate(
+    tx: &mpsc::Sender<Message>,
+    sticker_queue: &mut StickerQueue,
+    stickers: &StickerSettings,
+    current_song: Option<&SongInQueue>,
+    value: u8,
+) -> anyhow::Result<()> {
+    let Some(song) = current_song else {
+        return Ok(());
+    };
+
+    let value = value.min(100);
+
+    if stickers.ratings_enabled {
+        sticker_queue.queue_set(
+            song.song.url().to_owned(),
+            stickers.rating_sticker.clone(),
+            value.to_string(),
+        );
+    }
+
+    if let Ok(info) = song.try_into() {
+        let message = if value >= 50 {
+            Message::love_track(info)
+        } else {
+            Message::unlove_track(info)
+        };
+        tx.send(message).await?;
+    }
+
+    Ok(())
+}
+
+/// Bumps the `playcount`/`lastplayed` stickers for a song that just got
+/// scrobbled, queuing the writes so a transiently-unreachable MPD doesn't
+/// lose them. `playcount` is queued as a relative bump rather than a value
+/// read up front, so two plays completing before the queue drains don't
+/// clobber each other.
+async fn record_play(sticker_queue: &mut StickerQueue, stickers: &StickerSettings, uri: &str) {
+    if !stickers.playcount_enabled {
+        return;
+    }
+
+    sticker_queue.queue_increment(uri.to_owned(), stickers.playcount_sticker.clone(), 1);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    sticker_queue.queue_set(uri.to_owned(), stickers.lastplayed_sticker.clone(), now.to_string());
+}
+
 async fn scrobble_task(
     rx: &mut mpsc::Receiver<Message>,
     work_queue: &mut WorkQueue,
-    prev_client: Option<LastFmClient>,
     sk_path: &Path,
+    username_path: &Path,
+    secret_backend: SecretBackend,
     max_retry_time: Duration,
     non_interactive: bool,
-) -> (Option<LastFmClient>, MsgHandleError) {
-    let client_future = LastFmClient::new(prev_client, sk_path, non_interactive);
+    force_reauth: bool,
+    control_state: &ControlState,
+) -> MsgHandleError {
+    let client_future = LastFmClient::new(
+        sk_path,
+        username_path,
+        secret_backend,
+        non_interactive,
+        force_reauth,
+    );
 
     tokio::pin!(client_future);
 
     let mut current_song = None;
 
     let mut retry_time = Duration::from_secs(15);
-    let mut client = match loop {
+    'auth: loop {
         select! {
-            r = &mut client_future => break r.map_err(Into::into),
+            r = &mut client_future => match r {
+                Ok(client) => {
+                    work_queue.set_primary(Box::new(client));
+                    control_state.set_authenticated(true);
+                    break 'auth;
+                }
+                Err(e) => {
+                    error!("{e}");
+                    return e.into();
+                }
+            },
             Some(msg) = rx.recv() => {
                 match msg {
                     Message::Scrobble(info, timestamp) => work_queue.add_scrobble(info, timestamp),
@@ -351,40 +597,33 @@ async fn scrobble_task(
                         if let Some(info) = info_opt.as_ref() {
                             info!("new song: {} - {}", info.artist, info.title);
                         }
+                        control_state.set_current_song(info_opt.clone()).await;
                         current_song = info_opt;
                     }
+                    Message::Flush => {}
                 };
             }
-            else => return (None, MsgHandleError::ChannelClosed),
-        }
-    } {
-        Ok(client) => client,
-        Err(e) => {
-            error!("{e}");
-            return (None, e);
-        }
-    };
-
-    if let Some(info) = current_song {
-        if let Ok(()) = client.now_playing(&info).await {
-            info!("updated now playing status successfully");
+            else => return MsgHandleError::ChannelClosed,
         }
     }
 
+    work_queue.set_now_playing(current_song);
+
     if work_queue.has_work() {
-        if let Err(e) = work_queue.do_work(&mut client).await {
+        if let Err(e) = work_queue.do_work().await {
             if !e.is_retryable() {
-                return (Some(client), e.into());
+                return e.into();
             }
         }
     }
+    control_state.set_queue_depth(work_queue.queue_depth());
 
     loop {
         retry_time = min(max_retry_time, retry_time);
 
-        retry_time = match handle_async_msg(rx, retry_time, work_queue, &mut client).await {
+        retry_time = match handle_async_msg(rx, retry_time, work_queue, control_state).await {
             Ok(t) => t,
-            Err(e) => break (Some(client), e),
+            Err(e) => break e,
         }
     }
 }
@@ -393,102 +632,59 @@ async fn handle_async_msg(
     rx: &mut mpsc::Receiver<Message>,
     retry_time: Duration,
     work_queue: &mut WorkQueue,
-    client: &mut LastFmClient,
+    control_state: &ControlState,
 ) -> Result<Duration, MsgHandleError> {
     let r = rx.recv();
     let t = tokio::time::sleep(retry_time);
 
-    if work_queue.has_work() {
-        select! {
-            Some(msg) = r => {
-                match msg {
-                    Message::Scrobble(info, timestamp) => work_queue.add_scrobble(info, timestamp),
-                    Message::TrackAction(action, info) => work_queue.add_action(action, info),
-                    Message::NowPlaying(info_opt) => {
-                        if let Some(info) = info_opt.as_ref() {
-                            info!("new song: {} - {}", info.artist, info.title);
-                        }
-                        work_queue.last_played = info_opt;
-
-                        // good heuristic for preventing calling do_work twice in quick succession
-                        //
-                        // we don't really care about retrying when now playing has changed without
-                        // any scrobbles
-                        return Ok(retry_time);
-                    }
-                };
-                match work_queue.do_work(client).await {
-                    Ok(_) => Ok(Duration::from_secs(15)),
-                    Err(e) => if e.is_retryable() {
-                        Ok(retry_time)
-                    } else {
-                        Err(e.into())
+    select! {
+        Some(msg) = r => {
+            match msg {
+                Message::Scrobble(info, timestamp) => work_queue.add_scrobble(info, timestamp),
+                Message::TrackAction(action, info) => work_queue.add_action(action, info),
+                Message::NowPlaying(info_opt) => {
+                    if let Some(info) = info_opt.as_ref() {
+                        info!("new song: {} - {}", info.artist, info.title);
                     }
+                    control_state.set_current_song(info_opt.clone()).await;
+                    work_queue.set_now_playing(info_opt);
+
+                    // good heuristic for preventing calling do_work twice in quick succession
+                    //
+                    // we don't really care about retrying when now playing has changed without
+                    // any scrobbles
+                    return Ok(retry_time);
                 }
-            },
-            () = t => match work_queue.do_work(client).await {
-                    Ok(_) => Ok(Duration::from_secs(15)),
-                    Err(e) => if e.is_retryable() {
-                        Ok(retry_time * 2)
-                    } else {
-                        Err(e.into())
-                    }
-                },
-            else => Err(MsgHandleError::ChannelClosed),
-        }
-    } else if let Some(msg) = r.await {
-        match msg {
-            Message::Scrobble(info, timestamp) => {
-                info!("scrobbling {} - {}", info.artist, info.title);
-                if let Err(e) = client.scrobble_one(&info, &timestamp).await {
-                    if e.is_retryable() {
-                        warn!("scrobble failed: {e}");
-                        work_queue.add_scrobble(info, timestamp);
-                    } else {
-                        error!("scrobble failed: {e}");
-                        work_queue.add_scrobble(info, timestamp);
-                        return Err(e.into());
-                    }
-                } else {
-                    info!("scrobbled successfully");
-                }
-            }
-            Message::TrackAction(action, info) => {
-                info!("{}ing {} - {}", action, info.artist, info.title);
-                if let Err(e) = client.do_track_action(action, &info).await {
-                    if e.is_retryable() {
-                        warn!("{action}e track failed: {e}");
-                        work_queue.add_action(action, info);
-                    } else {
-                        error!("{action}e track failed: {e}");
-                        work_queue.add_action(action, info);
-                        return Err(e.into());
-                    }
+                Message::Flush => {}
+            };
+            let result = match work_queue.do_work().await {
+                Ok(()) => Ok(Duration::from_secs(15)),
+                Err(e) => if e.is_retryable() {
+                    #[cfg(feature = "metrics")]
+                    metrics::inc_api_retries();
+                    Ok(retry_time)
                 } else {
-                    info!("{action}ed successfully");
+                    Err(e.into())
                 }
-            }
-            Message::NowPlaying(Some(info)) => {
-                info!("new song: {} - {}", info.artist, info.title);
-                if let Err(e) = client.now_playing(&info).await {
-                    work_queue.last_played = Some(info);
-                    if e.is_retryable() {
-                        warn!("updating now playing failed: {e}");
-                    } else {
-                        error!("updating now playing failed: {e}");
-                        return Err(e.into());
-                    }
+            };
+            control_state.set_queue_depth(work_queue.queue_depth());
+            result
+        },
+        () = t => {
+            let result = match work_queue.do_work().await {
+                Ok(()) => Ok(Duration::from_secs(15)),
+                Err(e) => if e.is_retryable() {
+                    #[cfg(feature = "metrics")]
+                    metrics::inc_api_retries();
+                    Ok(retry_time * 2)
                 } else {
-                    info!("updated now playing status successfully");
+                    Err(e.into())
                 }
-            }
-            Message::NowPlaying(None) => {
-                work_queue.last_played = None;
-            }
-        }
-        Ok(Duration::from_secs(15))
-    } else {
-        Err(MsgHandleError::ChannelClosed)
+            };
+            control_state.set_queue_depth(work_queue.queue_depth());
+            result
+        },
+        else => Err(MsgHandleError::ChannelClosed),
     }
 }
 
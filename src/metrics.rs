@@ -0,0 +1,132 @@
+use std::{
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::{info, warn};
+use reqwest::Client as HttpClient;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time::interval,
+};
+
+use crate::settings::MetricsSettings;
+
+static SCROBBLES_SUBMITTED: AtomicU64 = AtomicU64::new(0);
+static SCROBBLES_PENDING: AtomicU64 = AtomicU64::new(0);
+static ACTIONS_PENDING: AtomicU64 = AtomicU64::new(0);
+static NOW_PLAYING_SUBMITTED: AtomicU64 = AtomicU64::new(0);
+static API_RETRIES: AtomicU64 = AtomicU64::new(0);
+static REAUTHS: AtomicU64 = AtomicU64::new(0);
+static FATAL_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+pub fn inc_scrobbles_submitted(n: u64) {
+    SCROBBLES_SUBMITTED.fetch_add(n, Ordering::Relaxed);
+}
+
+pub fn set_scrobbles_pending(n: u64) {
+    SCROBBLES_PENDING.store(n, Ordering::Relaxed);
+}
+
+pub fn set_actions_pending(n: u64) {
+    ACTIONS_PENDING.store(n, Ordering::Relaxed);
+}
+
+pub fn inc_now_playing_submitted() {
+    NOW_PLAYING_SUBMITTED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn inc_api_retries() {
+    API_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn inc_reauths() {
+    REAUTHS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn inc_fatal_errors() {
+    FATAL_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn render() -> String {
+    format!(
+        "# TYPE scritches_scrobbles_submitted_total counter\n\
+         scritches_scrobbles_submitted_total {}\n\
+         # TYPE scritches_scrobbles_pending gauge\n\
+         scritches_scrobbles_pending {}\n\
+         # TYPE scritches_actions_pending gauge\n\
+         scritches_actions_pending {}\n\
+         # TYPE scritches_now_playing_submitted_total counter\n\
+         scritches_now_playing_submitted_total {}\n\
+         # TYPE scritches_api_retries_total counter\n\
+         scritches_api_retries_total {}\n\
+         # TYPE scritches_reauths_total counter\n\
+         scritches_reauths_total {}\n\
+         # TYPE scritches_fatal_errors_total counter\n\
+         scritches_fatal_errors_total {}\n",
+        SCROBBLES_SUBMITTED.load(Ordering::Relaxed),
+        SCROBBLES_PENDING.load(Ordering::Relaxed),
+        ACTIONS_PENDING.load(Ordering::Relaxed),
+        NOW_PLAYING_SUBMITTED.load(Ordering::Relaxed),
+        API_RETRIES.load(Ordering::Relaxed),
+        REAUTHS.load(Ordering::Relaxed),
+        FATAL_ERRORS.load(Ordering::Relaxed),
+    )
+}
+
+async fn push_once(client: &HttpClient, settings: &MetricsSettings) {
+    let url = format!(
+        "{}/metrics/job/{}/instance/{}",
+        settings.pushgateway_url.trim_end_matches('/'),
+        settings.job,
+        settings.instance,
+    );
+
+    if let Err(e) = client.put(url).body(render()).send().await {
+        warn!("failed to push metrics to pushgateway: {e}");
+    }
+}
+
+/// Periodically pushes the current metric snapshot to the configured
+/// Pushgateway, once every `interval_duration`.
+pub async fn push_task(settings: MetricsSettings, interval_duration: Duration) {
+    let client = HttpClient::new();
+    let mut ticker = interval(interval_duration);
+
+    loop {
+        ticker.tick().await;
+        push_once(&client, &settings).await;
+    }
+}
+
+/// Serves the current metric snapshot in the Prometheus text exposition
+/// format, for operators who'd rather scrape scritches directly than run a
+/// Pushgateway.
+pub async fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving metrics on http://{addr}/");
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            if let Err(e) = stream.read(&mut buf).await {
+                warn!("metrics client read failed: {e}");
+                return;
+            }
+
+            let body = render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len(),
+            );
+
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                warn!("failed to write metrics response: {e}");
+            }
+        });
+    }
+}
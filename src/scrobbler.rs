@@ -0,0 +1,133 @@
+use std::fmt;
+
+use async_trait::async_trait;
+
+use crate::last_fm::{self, Action, BasicInfo, SongInfo};
+
+/// Why a scrobble was ignored by a backend, see [`IgnoredReason::is_transient`]
+/// for which reasons are worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoredReason {
+    ArtistIgnored,
+    TrackIgnored,
+    TimestampTooOld,
+    TimestampTooNew,
+    RateLimited,
+}
+
+impl IgnoredReason {
+    /// whether this is worth retrying later rather than dropping for good
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::RateLimited | Self::TimestampTooNew)
+    }
+}
+
+impl fmt::Display for IgnoredReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::ArtistIgnored => "artist ignored",
+            Self::TrackIgnored => "track ignored",
+            Self::TimestampTooOld => "timestamp too old",
+            Self::TimestampTooNew => "timestamp too far in the future",
+            Self::RateLimited => "daily scrobble limit reached",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Per-track outcome of submitting a scrobble to a backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrobbleOutcome {
+    Accepted,
+    /// ignored for a transient reason, should stay queued and be retried
+    Retry,
+    /// permanently ignored, should be dropped
+    Dropped(IgnoredReason),
+}
+
+/// Most scrobbling backends (last.fm included) cap a batch submission at 50
+/// tracks per request; [`crate::work_queue`] chunks to this size when
+/// draining a backlog.
+pub const MAX_SCROBBLE_BATCH: usize = 50;
+
+/// A play pulled from a backend's own listen history, used to reconcile the
+/// offline queue against scrobbles another client already submitted.
+#[derive(Debug, Clone)]
+pub struct RecentTrack {
+    pub artist: String,
+    pub title: String,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("{0}")]
+    Retry(String),
+
+    #[error("{0}")]
+    Reauth(String),
+
+    #[error("{0}")]
+    Fatal(String),
+}
+
+impl Error {
+    #[inline]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Http(_) | Self::Retry(_))
+    }
+
+    #[inline]
+    pub fn is_reauth(&self) -> bool {
+        matches!(self, Self::Reauth(_))
+    }
+}
+
+impl From<last_fm::Error> for Error {
+    fn from(e: last_fm::Error) -> Self {
+        match e {
+            last_fm::Error::Http(err) => Self::Http(err),
+            last_fm::Error::ApiRetry(code, msg) => Self::Retry(format!("{msg} (error {code})")),
+            last_fm::Error::ApiReauth(code, msg) => Self::Reauth(format!("{msg} (error {code})")),
+            last_fm::Error::ApiFatal(code, msg) => Self::Fatal(format!("{msg} (error {code})")),
+            last_fm::Error::TooManyScrobbles(n) => Self::Fatal(format!(
+                "too many scrobbles in batch. maximum is {MAX_SCROBBLE_BATCH} got {n}"
+            )),
+            last_fm::Error::Ser(err) => Self::Fatal(format!("error deserializing response: {err}")),
+            last_fm::Error::NonInteractive => {
+                Self::Fatal("need interaction for authentication".into())
+            }
+        }
+    }
+}
+
+/// A service that scritches can report plays to.
+#[async_trait]
+pub trait Scrobbler: Send {
+    /// short name for logging, e.g. "last.fm"
+    fn name(&self) -> &'static str;
+
+    async fn now_playing(&mut self, info: &SongInfo) -> Result<(), Error>;
+
+    async fn scrobble_many(
+        &mut self,
+        infos: &[(SongInfo, String)],
+    ) -> Result<Vec<ScrobbleOutcome>, Error>;
+
+    async fn do_track_action(&mut self, action: Action, info: &BasicInfo) -> Result<(), Error>;
+
+    /// whether this backend can provide its own recent play history for
+    /// [`Scrobbler::recent_tracks`]
+    fn supports_reconcile(&self) -> bool {
+        false
+    }
+
+    /// fetch one page of this backend's recent play history, returning the
+    /// tracks on that page and the total number of pages
+    async fn recent_tracks(&self, _page: u32) -> Result<(Vec<RecentTrack>, u32), Error> {
+        Ok((Vec::new(), 1))
+    }
+}
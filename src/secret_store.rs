@@ -0,0 +1,133 @@
+use std::{collections::HashMap, path::Path};
+
+use log::warn;
+use secret_service::{blocking::SecretService, EncryptionType, Error as SsError};
+use serde::Deserialize;
+
+const ATTR_SERVICE: &str = "service";
+const ATTR_SERVICE_VALUE: &str = "scritches";
+const ATTR_USERNAME: &str = "username";
+
+/// Where to persist the last.fm session key.
+#[derive(Debug, Clone, Copy, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum SecretBackend {
+    File,
+    SecretService,
+}
+
+/// A pluggable place to load and store the last.fm session key.
+pub trait SecretStore {
+    fn load(&self) -> Option<String>;
+    fn store(&self, sk: &str);
+}
+
+pub struct FileStore<'a> {
+    path: &'a Path,
+}
+
+impl<'a> FileStore<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self { path }
+    }
+}
+
+impl SecretStore for FileStore<'_> {
+    fn load(&self) -> Option<String> {
+        match std::fs::read_to_string(self.path) {
+            Err(e) => {
+                warn!(
+                    "couldn't read session key from `{}`: {e}",
+                    self.path.display()
+                );
+                None
+            }
+            Ok(sk) => Some(sk),
+        }
+    }
+
+    fn store(&self, sk: &str) {
+        if let Err(e) = std::fs::write(self.path, sk) {
+            warn!("failed to persist session key: {e}");
+        }
+    }
+}
+
+/// Stores the session key in the freedesktop Secret Service, falling back to a
+/// plain file when the bus is unavailable (e.g. no keyring daemon running).
+pub struct KeyringStore<'a> {
+    username: Option<&'a str>,
+    fallback: FileStore<'a>,
+}
+
+impl<'a> KeyringStore<'a> {
+    pub fn new(username: Option<&'a str>, path: &'a Path) -> Self {
+        Self {
+            username,
+            fallback: FileStore::new(path),
+        }
+    }
+
+    fn try_load(&self) -> Result<Option<String>, SsError> {
+        let ss = SecretService::connect(EncryptionType::Dh)?;
+        let collection = ss.get_default_collection()?;
+        if collection.is_locked()? {
+            collection.unlock()?;
+        }
+
+        let mut attrs = HashMap::from([(ATTR_SERVICE, ATTR_SERVICE_VALUE)]);
+        if let Some(username) = self.username {
+            attrs.insert(ATTR_USERNAME, username);
+        }
+
+        let items = collection.search_items(attrs)?;
+        let Some(item) = items.first() else {
+            return Ok(None);
+        };
+
+        Ok(Some(String::from_utf8_lossy(&item.get_secret()?).into_owned()))
+    }
+
+    fn try_store(&self, sk: &str) -> Result<(), SsError> {
+        let ss = SecretService::connect(EncryptionType::Dh)?;
+        let collection = ss.get_default_collection()?;
+        if collection.is_locked()? {
+            collection.unlock()?;
+        }
+
+        let mut attrs = HashMap::from([(ATTR_SERVICE, ATTR_SERVICE_VALUE)]);
+        if let Some(username) = self.username {
+            attrs.insert(ATTR_USERNAME, username);
+        }
+
+        collection.create_item(
+            "scritches last.fm session key",
+            attrs,
+            sk.as_bytes(),
+            true,
+            "text/plain",
+        )?;
+
+        Ok(())
+    }
+}
+
+impl SecretStore for KeyringStore<'_> {
+    fn load(&self) -> Option<String> {
+        match self.try_load() {
+            Ok(sk) => sk,
+            Err(e) => {
+                warn!("couldn't reach secret service: {e}, falling back to file");
+                self.fallback.load()
+            }
+        }
+    }
+
+    fn store(&self, sk: &str) {
+        if let Err(e) = self.try_store(sk) {
+            warn!("couldn't reach secret service: {e}, falling back to file");
+            self.fallback.store(sk);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use serde::Deserialize;
 
 use std::path::PathBuf;
 
+use crate::secret_store::SecretBackend;
+
 #[derive(Debug, Parser)]
 #[command(version)]
 pub struct Args {
@@ -31,13 +33,45 @@ pub struct Args {
     #[arg(short, long)]
     key: Option<String>,
 
+    /// Where to persist the last.fm session key
+    #[arg(long, value_enum)]
+    secret_backend: Option<SecretBackend>,
+
     /// Maximum time between retries
     #[arg(short, long)]
     time: Option<u64>,
 
+    /// ListenBrainz user token, to also scrobble there alongside last.fm
+    #[arg(long)]
+    listenbrainz_token: Option<String>,
+
     /// Exit program if user needs to (re)authorize
     #[arg(short = 'i', long)]
     pub non_interactive: bool,
+
+    /// Unix socket to serve the control API on
+    #[arg(long)]
+    control_socket: Option<String>,
+}
+
+/// Which MPD stickers scritches writes for local play history
+/// (`playcount_enabled` / `ratings_enabled`) and under what names.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StickerSettings {
+    pub playcount_enabled: bool,
+    pub ratings_enabled: bool,
+    pub playcount_sticker: String,
+    pub lastplayed_sticker: String,
+    pub rating_sticker: String,
+}
+
+/// Where to push metrics for scraping, see the `metrics` feature.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricsSettings {
+    pub pushgateway_url: String,
+    pub job: String,
+    pub instance: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,8 +80,23 @@ pub struct Settings {
     pub mpd_socket: Option<PathBuf>,
     pub mpd_password: Option<String>,
     pub queue_path: PathBuf,
+    pub sticker_queue_path: PathBuf,
     pub sk_path: PathBuf,
+    pub username_path: PathBuf,
+    pub secret_backend: SecretBackend,
     pub max_retry_time: u64,
+    pub reconcile_recent_tracks: bool,
+    pub listenbrainz_token: Option<String>,
+    pub stickers: StickerSettings,
+    /// Path to serve the control socket on, see `control` module. Unset
+    /// disables the control socket entirely.
+    pub control_socket: Option<PathBuf>,
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<MetricsSettings>,
+    /// Address to serve the Prometheus text exposition format on, e.g.
+    /// `127.0.0.1:9090`.
+    #[cfg(feature = "metrics")]
+    pub metrics_addr: Option<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -83,6 +132,13 @@ impl Settings {
                     .to_str()
                     .ok_or(Error::QueuePath)?,
             )?
+            .set_default(
+                "sticker_queue_path",
+                xdg_dirs
+                    .place_state_file("sticker_queue")?
+                    .to_str()
+                    .ok_or(Error::QueuePath)?,
+            )?
             .set_default(
                 "sk_path",
                 xdg_dirs
@@ -90,7 +146,28 @@ impl Settings {
                     .to_str()
                     .ok_or(Error::KeyPath)?,
             )?
-            .set_default("max_retry_time", 960)?;
+            .set_default(
+                "username_path",
+                xdg_dirs
+                    .place_state_file("username")?
+                    .to_str()
+                    .ok_or(Error::KeyPath)?,
+            )?
+            .set_default("secret_backend", "file")?
+            .set_default("max_retry_time", 960)?
+            .set_default("reconcile_recent_tracks", false)?
+            .set_default("stickers.playcount_enabled", false)?
+            .set_default("stickers.ratings_enabled", false)?
+            .set_default("stickers.playcount_sticker", "playcount")?
+            .set_default("stickers.lastplayed_sticker", "lastplayed")?
+            .set_default("stickers.rating_sticker", "rating")?;
+
+        #[cfg(feature = "metrics")]
+        {
+            config_builder = config_builder
+                .set_default("metrics.job", "scritches")?
+                .set_default("metrics.instance", "default")?;
+        }
 
         if let Some(addr) = args.addr {
             config_builder = config_builder.set_override("mpd_addr", addr)?;
@@ -112,10 +189,26 @@ impl Settings {
             config_builder = config_builder.set_override("sk_path", sk_path)?;
         }
 
+        if let Some(backend) = args.secret_backend {
+            let backend = match backend {
+                SecretBackend::File => "file",
+                SecretBackend::SecretService => "secret-service",
+            };
+            config_builder = config_builder.set_override("secret_backend", backend)?;
+        }
+
         if let Some(time) = args.time {
             config_builder = config_builder.set_override("max_retry_time", time)?;
         }
 
+        if let Some(token) = args.listenbrainz_token {
+            config_builder = config_builder.set_override("listenbrainz_token", token)?;
+        }
+
+        if let Some(control_socket) = args.control_socket {
+            config_builder = config_builder.set_override("control_socket", control_socket)?;
+        }
+
         config_builder = config_builder.add_source(if let Some(config_path) = args.config {
             config::File::with_name(&config_path)
         } else {
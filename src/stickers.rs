@@ -0,0 +1,122 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Seek},
+    path::Path,
+};
+
+use log::{error, warn};
+use mpd_client::{
+    client::{Client as MpdClient, CommandError},
+    commands::{StickerGet, StickerSet},
+};
+use serde::{Deserialize, Serialize};
+
+/// One sticker write waiting to be applied to MPD, queued so a transient
+/// failure (MPD briefly unreachable) doesn't lose the update.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Op {
+    /// overwrite the sticker with a fixed value, e.g. `lastplayed` or
+    /// `rating`, where only the most recent write matters
+    Set { uri: String, name: String, value: String },
+    /// bump a numeric sticker by `delta` relative to whatever's actually
+    /// stored in MPD when this op is applied, e.g. `playcount` — resolving
+    /// against the live value at apply time (rather than a value baked in
+    /// when queued) means two plays completing before the queue drains both
+    /// land instead of one clobbering the other
+    Increment { uri: String, name: String, delta: i64 },
+}
+
+/// Queued MPD sticker writes, drained the same way `WorkQueue` drains a
+/// scrobble backend: on failure the remaining ops stay queued for the next
+/// attempt instead of being dropped. Persisted to disk the same way too, so
+/// a pending playcount/lastplayed/rating write survives a daemon restart.
+#[derive(Debug)]
+pub struct StickerQueue {
+    ops: VecDeque<Op>,
+    queue_file: File,
+}
+
+impl StickerQueue {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let ops = match File::open(path) {
+            Ok(f) => bincode::deserialize_from(f).unwrap_or_else(|e| {
+                warn!("unable to read sticker queue file: {e}");
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+
+        let queue_file = File::create(path)?;
+
+        Ok(Self { ops, queue_file })
+    }
+
+    fn write(&mut self) {
+        if let Err(e) = self.try_write() {
+            error!("failed to save sticker queue: {e}");
+        }
+    }
+
+    fn try_write(&mut self) -> bincode::Result<()> {
+        self.queue_file.set_len(0)?;
+        self.queue_file.rewind()?;
+        bincode::serialize_into(&self.queue_file, &self.ops)
+    }
+
+    pub fn queue_set(&mut self, uri: String, name: String, value: String) {
+        self.ops.push_back(Op::Set { uri, name, value });
+        self.write();
+    }
+
+    /// Queues a relative bump to a numeric sticker, e.g. `playcount`,
+    /// resolved against MPD's current value when the op is drained rather
+    /// than a value computed up front.
+    pub fn queue_increment(&mut self, uri: String, name: String, delta: i64) {
+        self.ops.push_back(Op::Increment { uri, name, delta });
+        self.write();
+    }
+
+    #[inline]
+    pub fn has_work(&self) -> bool {
+        !self.ops.is_empty()
+    }
+
+    /// Applies queued sticker writes in order, stopping on the first
+    /// failure so it can be retried once MPD is reachable again.
+    pub async fn do_work(&mut self, client: &MpdClient) -> Result<(), CommandError> {
+        while let Some(op) = self.ops.front() {
+            let (uri, name, value) = match op {
+                Op::Set { uri, name, value } => (uri.clone(), name.clone(), value.clone()),
+                Op::Increment { uri, name, delta } => {
+                    let current = read_u64(client, uri, name).await;
+                    let value = current.saturating_add_signed(*delta);
+                    (uri.clone(), name.clone(), value.to_string())
+                }
+            };
+
+            client.command(StickerSet { uri, name, value }).await?;
+            self.ops.pop_front();
+            self.write();
+        }
+        Ok(())
+    }
+}
+
+/// Reads a sticker as a `u64`, defaulting to 0 if it's unset or unparsable
+/// (e.g. the first play of a song with no `playcount` sticker yet).
+pub async fn read_u64(client: &MpdClient, uri: &str, name: &str) -> u64 {
+    match client
+        .command(StickerGet {
+            uri: uri.to_owned(),
+            name: name.to_owned(),
+        })
+        .await
+    {
+        Ok(value) => value.parse().unwrap_or(0),
+        Err(e) => {
+            warn!("couldn't read `{name}` sticker for {uri}: {e}");
+            0
+        }
+    }
+}
@@ -6,38 +6,74 @@ use std::{
     path::Path,
 };
 
-use log::{error, info, trace, warn};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
 
-use crate::last_fm::{Action, BasicInfo, Client as LastFmClient, Error as LastFmError, SongInfo};
+use crate::{
+    last_fm::{Action, BasicInfo, SongInfo},
+    scrobbler::{Error as ScrobblerError, ScrobbleOutcome, Scrobbler, MAX_SCROBBLE_BATCH},
+};
 
-#[derive(Debug)]
-pub struct WorkQueue {
+/// how far apart two timestamps for the same track can be and still be
+/// considered the same play, to absorb clock skew between scritches and a
+/// backend's clock
+const RECONCILE_TOLERANCE_SECS: i64 = 60;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackendQueue {
     scrobble_queue: VecDeque<(SongInfo, String)>,
     action_queue: VecDeque<(Action, BasicInfo)>,
+    #[serde(skip)]
+    now_playing: Option<SongInfo>,
+}
+
+/// A backend and the work queued up for it. `scrobbler` is `None` until the
+/// backend is ready to use (e.g. the primary backend until its first
+/// authentication completes); queueing keeps working in the meantime.
+struct Backend {
+    scrobbler: Option<Box<dyn Scrobbler>>,
+    queue: BackendQueue,
+}
+
+pub struct WorkQueue {
+    backends: Vec<Backend>,
+    pending_queues: VecDeque<BackendQueue>,
     queue_file: File,
-    pub last_played: Option<SongInfo>,
+    reconcile: bool,
+    /// whether `reconcile()` has already run for the current backlog, so a
+    /// normal (non-offline) drain doesn't pay for a `user.getRecentTracks`
+    /// fetch on every single scrobble; cleared when the queue goes from
+    /// empty to non-empty again or the primary backend changes
+    reconciled: bool,
 }
 
 impl WorkQueue {
-    pub fn new(path: &Path) -> io::Result<Self> {
-        let (scrobble_queue, action_queue) = match File::open(path) {
+    pub fn new(path: &Path, reconcile: bool) -> io::Result<Self> {
+        let mut pending_queues: VecDeque<BackendQueue> = match File::open(path) {
             Ok(f) => bincode::deserialize_from(f).unwrap_or_else(|e| {
                 warn!("unable to read queue file: {e}");
-                (VecDeque::new(), VecDeque::new())
+                VecDeque::new()
             }),
-            Err(_) => (VecDeque::new(), VecDeque::new()),
+            Err(_) => VecDeque::new(),
         };
 
         let queue_file = File::create(path)?;
 
+        let primary = Backend {
+            scrobbler: None,
+            queue: pending_queues.pop_front().unwrap_or_default(),
+        };
+
         let mut res = Self {
-            scrobble_queue,
-            action_queue,
+            backends: vec![primary],
+            pending_queues,
             queue_file,
-            last_played: None,
+            reconcile,
+            reconciled: false,
         };
 
         res.write();
+        res.report_queue_depth();
         Ok(res)
     }
 
@@ -50,68 +86,259 @@ impl WorkQueue {
     fn try_write(&mut self) -> bincode::Result<()> {
         self.queue_file.set_len(0)?;
         self.queue_file.rewind()?;
-        bincode::serialize_into(
-            &self.queue_file,
-            &(&self.scrobble_queue, &self.action_queue),
-        )
+        let queues: Vec<&BackendQueue> = self.backends.iter().map(|b| &b.queue).collect();
+        bincode::serialize_into(&self.queue_file, &queues)
+    }
+
+    /// Hands over the primary backend once it's ready. Anything queued for
+    /// it while it was absent stays queued.
+    pub fn set_primary(&mut self, scrobbler: Box<dyn Scrobbler>) {
+        info!("{} is now the primary scrobbling backend", scrobbler.name());
+        self.backends[0].scrobbler = Some(scrobbler);
+        self.reconciled = false;
+    }
+
+    /// Registers an additional backend to scrobble to alongside the primary
+    /// one, restoring any work still queued for it from the last run.
+    pub fn add_backend(&mut self, scrobbler: Box<dyn Scrobbler>) {
+        info!(
+            "registered {} as an additional scrobbling backend",
+            scrobbler.name()
+        );
+        self.backends.push(Backend {
+            scrobbler: Some(scrobbler),
+            queue: self.pending_queues.pop_front().unwrap_or_default(),
+        });
+    }
+
+    /// Number of scrobbles and actions still queued for the primary backend.
+    pub fn queue_depth(&self) -> usize {
+        let primary = &self.backends[0].queue;
+        primary.scrobble_queue.len() + primary.action_queue.len()
     }
 
     #[inline]
     pub fn has_work(&self) -> bool {
-        !self.scrobble_queue.is_empty()
-            || !self.action_queue.is_empty()
-            || self.last_played.is_some()
-    }
-
-    pub async fn do_work(&mut self, client: &mut LastFmClient) -> Result<(), LastFmError> {
-        let mut count = 0;
-        while !self.scrobble_queue.is_empty() {
-            let range = ..min(50, self.scrobble_queue.len());
-            let batch = &self.scrobble_queue.make_contiguous()[range];
-            if let Err(e) = client.scrobble_many(batch).await {
-                self.write();
-                if e.is_retryable() {
-                    warn!("scrobbling queue failed: {e}");
-                } else {
-                    error!("scrobbling queue failed: {e}");
-                }
-                if count > 0 {
-                    info!("succesfully scrobbled {count} songs from queue");
-                }
-                return Err(e);
-            }
-            count += range.end;
-            self.scrobble_queue.drain(range);
+        self.backends.iter().any(|b| {
+            !b.queue.scrobble_queue.is_empty()
+                || !b.queue.action_queue.is_empty()
+                || b.queue.now_playing.is_some()
+        })
+    }
+
+    /// Fetches the primary backend's recent play history and drops anything
+    /// from its queue that another client already scrobbled while we were
+    /// offline, to avoid submitting a duplicate.
+    async fn reconcile(&mut self) -> Result<(), ScrobblerError> {
+        if self.reconciled || !self.reconcile || self.backends[0].queue.scrobble_queue.is_empty() {
+            return Ok(());
         }
-        info!("succesfully scrobbled {count} songs from queue");
 
-        while let Some((action, info)) = self.action_queue.front() {
-            if let Err(e) = client.do_track_action(*action, info).await {
-                error!("{action}e track failed: {e}");
-                self.write();
-                return Err(e);
+        let supports_reconcile = self.backends[0]
+            .scrobbler
+            .as_ref()
+            .is_some_and(|s| s.supports_reconcile());
+        if !supports_reconcile {
+            return Ok(());
+        }
+
+        let Some(oldest) = self.backends[0]
+            .queue
+            .scrobble_queue
+            .front()
+            .and_then(|(_, ts)| ts.parse::<i64>().ok())
+        else {
+            return Ok(());
+        };
+
+        let mut recent = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let (tracks, total_pages) = self.backends[0]
+                .scrobbler
+                .as_ref()
+                .expect("checked above")
+                .recent_tracks(page)
+                .await?;
+
+            let hit_floor = tracks
+                .iter()
+                .any(|t| t.timestamp < oldest - RECONCILE_TOLERANCE_SECS);
+
+            recent.extend(tracks);
+
+            if hit_floor || page >= total_pages {
+                break;
             }
+            page += 1;
         }
-        self.write();
 
-        if let Some(info) = self.last_played.as_ref() {
-            client.now_playing(info).await?;
-            self.last_played = None;
-            info!("succesfully updated now playing status");
+        let backend = &mut self.backends[0];
+        let before = backend.queue.scrobble_queue.len();
+        backend.queue.scrobble_queue.retain(|(info, timestamp)| {
+            let Ok(ts) = timestamp.parse::<i64>() else {
+                return true;
+            };
+            !recent.iter().any(|t| {
+                t.artist.eq_ignore_ascii_case(&info.artist)
+                    && t.title.eq_ignore_ascii_case(&info.title)
+                    && (t.timestamp - ts).abs() <= RECONCILE_TOLERANCE_SECS
+            })
+        });
+        let removed = before - backend.queue.scrobble_queue.len();
+
+        if removed > 0 {
+            let name = backend.scrobbler.as_ref().expect("checked above").name();
+            info!("dropped {removed} scrobble(s) already present in {name}'s history");
+            self.write();
         }
 
+        self.reconciled = true;
+
         Ok(())
     }
 
+    /// Drains every backend's queue. A backend that isn't ready yet is left
+    /// alone and tried again on the next pass. Only the primary backend's
+    /// (index 0) errors propagate, since it's the only one scritches can
+    /// reauth or give up on; the rest are best-effort mirrors of it.
+    pub async fn do_work(&mut self) -> Result<(), ScrobblerError> {
+        self.reconcile().await?;
+
+        let mut primary_err = None;
+        for (i, backend) in self.backends.iter_mut().enumerate() {
+            let Some(scrobbler) = backend.scrobbler.as_mut() else {
+                continue;
+            };
+
+            if let Err(e) = drain_backend(scrobbler.as_mut(), &mut backend.queue).await {
+                if i == 0 {
+                    if e.is_retryable() {
+                        warn!("scrobbling queue failed: {e}");
+                    } else {
+                        error!("scrobbling queue failed: {e}");
+                    }
+                    primary_err = Some(e);
+                    continue;
+                }
+                warn!("{} failed, will retry later: {e}", scrobbler.name());
+            }
+        }
+
+        self.write();
+        self.report_queue_depth();
+
+        match primary_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     pub fn add_scrobble(&mut self, info: SongInfo, timestamp: String) {
         info!("added scrobble {} - {} to queue", info.artist, info.title);
-        self.scrobble_queue.push_back((info, timestamp));
+        if self.backends[0].queue.scrobble_queue.is_empty() {
+            self.reconciled = false;
+        }
+        for backend in &mut self.backends {
+            backend
+                .queue
+                .scrobble_queue
+                .push_back((info.clone(), timestamp.clone()));
+        }
         self.write();
+        self.report_queue_depth();
     }
 
     pub fn add_action(&mut self, action: Action, info: BasicInfo) {
         info!("added {action}e {} - {} to queue", info.artist, info.title);
-        self.action_queue.push_back((action, info));
+        for backend in &mut self.backends {
+            backend.queue.action_queue.push_back((action, info.clone()));
+        }
         self.write();
+        self.report_queue_depth();
+    }
+
+    /// Updates the metrics gauges to the primary backend's current queue
+    /// depth, called after every [`Self::write`].
+    #[cfg(feature = "metrics")]
+    fn report_queue_depth(&self) {
+        let primary = &self.backends[0].queue;
+        crate::metrics::set_scrobbles_pending(primary.scrobble_queue.len() as u64);
+        crate::metrics::set_actions_pending(primary.action_queue.len() as u64);
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    fn report_queue_depth(&self) {}
+
+    pub fn set_now_playing(&mut self, info: Option<SongInfo>) {
+        for backend in &mut self.backends {
+            backend.queue.now_playing.clone_from(&info);
+        }
     }
 }
+
+/// Submits everything queued for one backend, scrobbles `MAX_SCROBBLE_BATCH`
+/// at a time so draining a long backlog takes a handful of requests instead
+/// of one per track.
+async fn drain_backend(
+    scrobbler: &mut dyn Scrobbler,
+    queue: &mut BackendQueue,
+) -> Result<(), ScrobblerError> {
+    let mut count = 0;
+    while !queue.scrobble_queue.is_empty() {
+        let len = min(MAX_SCROBBLE_BATCH, queue.scrobble_queue.len());
+        let batch = &queue.scrobble_queue.make_contiguous()[..len];
+        let outcomes = scrobbler.scrobble_many(batch).await?;
+
+        // accepted and permanently-ignored scrobbles are done with; a
+        // transiently-ignored one stays queued and is retried on the next
+        // pass. tracked per-entry rather than as a single cutoff, so one
+        // straggler in the middle of a batch doesn't force resubmitting
+        // everything after it too
+        let mut retried = VecDeque::new();
+        for (entry, outcome) in queue.scrobble_queue.drain(..len).zip(&outcomes) {
+            match outcome {
+                ScrobbleOutcome::Accepted => count += 1,
+                ScrobbleOutcome::Dropped(reason) => {
+                    warn!(
+                        "{} permanently ignored a queued scrobble: {reason}",
+                        scrobbler.name()
+                    );
+                    count += 1;
+                }
+                ScrobbleOutcome::Retry => retried.push_back(entry),
+            }
+        }
+
+        let had_retry = !retried.is_empty();
+        retried.append(&mut queue.scrobble_queue);
+        queue.scrobble_queue = retried;
+
+        if had_retry {
+            break;
+        }
+    }
+    if count > 0 {
+        info!("succesfully scrobbled {count} songs to {}", scrobbler.name());
+        #[cfg(feature = "metrics")]
+        crate::metrics::inc_scrobbles_submitted(count as u64);
+    }
+
+    while let Some((action, info)) = queue.action_queue.front().cloned() {
+        scrobbler.do_track_action(action, &info).await?;
+        queue.action_queue.pop_front();
+    }
+
+    if let Some(info) = queue.now_playing.take() {
+        scrobbler.now_playing(&info).await?;
+        info!(
+            "succesfully updated now playing status for {}",
+            scrobbler.name()
+        );
+        #[cfg(feature = "metrics")]
+        crate::metrics::inc_now_playing_submitted();
+    }
+
+    Ok(())
+}